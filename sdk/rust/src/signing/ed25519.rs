@@ -0,0 +1,230 @@
+extern crate ed25519_dalek;
+
+use self::ed25519_dalek::Keypair;
+use self::ed25519_dalek::PublicKey as DalekPublicKey;
+use self::ed25519_dalek::SecretKey as DalekSecretKey;
+use self::ed25519_dalek::Signature as DalekSignature;
+use self::ed25519_dalek::SignatureError;
+
+use super::PrivateKey;
+use super::PublicKey;
+use super::Algorithm;
+use super::Error;
+use super::hex_str_to_bytes;
+use super::bytes_to_hex_str;
+use super::check_key_algorithm;
+use super::Zeroizing;
+
+impl From<SignatureError> for Error {
+    fn from(e: SignatureError) -> Self {
+        Error::SigningError(Box::new(e))
+    }
+}
+
+pub struct Ed25519PrivateKey {
+    private: Zeroizing
+}
+
+impl Ed25519PrivateKey {
+    pub fn from_hex(s: &str) -> Result<Self, Error> {
+        let key_bytes = hex_str_to_bytes(s)?;
+        if key_bytes.len() != 32 {
+            return Err(Error::ParseError(
+                format!("invalid key length: {}", key_bytes.len())));
+        }
+        Ok(Ed25519PrivateKey{
+            private: Zeroizing::new(key_bytes)
+        })
+    }
+}
+
+impl PrivateKey for Ed25519PrivateKey {
+    fn get_algorithm_name(&self) -> &str {
+        "ed25519"
+    }
+
+    fn as_hex(&self) -> String {
+        self.private.as_hex()
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        self.private.as_slice()
+    }
+}
+
+pub struct Ed25519PublicKey {
+    public: Vec<u8>
+}
+
+impl Ed25519PublicKey {
+    pub fn from_hex(s: &str) -> Result<Self, Error> {
+        let key_bytes = hex_str_to_bytes(s)?;
+        if key_bytes.len() != 32 {
+            return Err(Error::ParseError(
+                format!("invalid key length: {}", key_bytes.len())));
+        }
+        Ok(Ed25519PublicKey{
+            public: key_bytes
+        })
+    }
+}
+
+impl PublicKey for Ed25519PublicKey {
+    fn get_algorithm_name(&self) -> &str {
+        "ed25519"
+    }
+
+    fn as_hex(&self) -> String {
+        bytes_to_hex_str(&self.public)
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        return &self.public;
+    }
+}
+
+fn to_keypair(key: &PrivateKey) -> Result<Keypair, Error> {
+    let secret = DalekSecretKey::from_bytes(key.as_slice())?;
+    let public = DalekPublicKey::from(&secret);
+    Ok(Keypair { secret: secret, public: public })
+}
+
+pub struct Ed25519Algorithm;
+
+impl Ed25519Algorithm {
+    pub fn new() -> Self {
+        Ed25519Algorithm
+    }
+}
+
+impl Algorithm for Ed25519Algorithm {
+    fn get_name(&self) -> &str {
+        "ed25519"
+    }
+
+    fn sign(&self, message: &[u8], key: &PrivateKey) -> Result<String, Error> {
+        check_key_algorithm(self.get_name(), key.get_algorithm_name())?;
+        let keypair = to_keypair(key)?;
+        let sig = keypair.sign(message);
+        Ok(bytes_to_hex_str(&sig.to_bytes()))
+    }
+
+    fn verify(&self, signature: &str, message: &[u8], key: &PublicKey) -> Result<bool, Error> {
+        check_key_algorithm(self.get_name(), key.get_algorithm_name())?;
+        let public = DalekPublicKey::from_bytes(key.as_slice())?;
+        let sig = DalekSignature::from_bytes(&hex_str_to_bytes(signature)?)?;
+
+        match public.verify(message, &sig) {
+            Ok(()) => Ok(true),
+            Err(_) => Ok(false)
+        }
+    }
+
+    fn get_public_key(&self, private_key: &PrivateKey) -> Result<Box<PublicKey>, Error> {
+        check_key_algorithm(self.get_name(), private_key.get_algorithm_name())?;
+        let keypair = to_keypair(private_key)?;
+        Ok(Box::new(Ed25519PublicKey{
+            public: keypair.public.to_bytes().to_vec()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod ed25519_test {
+    use super::Ed25519PrivateKey;
+    use super::Ed25519PublicKey;
+    use super::super::PrivateKey;
+    use super::super::PublicKey;
+    use super::super::create_algorithm;
+
+    static KEY1_PRIV_HEX: &'static str =
+        "a64ba1c3a83d8881469dff6229876ed1c8db13b98be737c378b21a9efd0b0afd";
+
+    static MSG1: &'static str = "test";
+
+    #[test]
+    fn hex_key() {
+        let priv_key = Ed25519PrivateKey::from_hex(KEY1_PRIV_HEX).unwrap();
+        assert_eq!(priv_key.get_algorithm_name(), "ed25519");
+        assert_eq!(priv_key.as_hex(), KEY1_PRIV_HEX);
+    }
+
+    #[test]
+    fn priv_to_public_key_is_deterministic() {
+        let algorithm = create_algorithm("ed25519").unwrap();
+        assert_eq!(algorithm.get_name(), "ed25519");
+
+        let priv_key = Ed25519PrivateKey::from_hex(KEY1_PRIV_HEX).unwrap();
+        let public_key1 = algorithm.get_public_key(&priv_key).unwrap();
+        let public_key2 = algorithm.get_public_key(&priv_key).unwrap();
+
+        assert_eq!(public_key1.as_hex(), public_key2.as_hex());
+        assert_eq!(public_key1.as_hex().len(), 64);
+    }
+
+    #[test]
+    fn signing_is_deterministic_and_verifies() {
+        let algorithm = create_algorithm("ed25519").unwrap();
+
+        let priv_key = Ed25519PrivateKey::from_hex(KEY1_PRIV_HEX).unwrap();
+        let pub_key = algorithm.get_public_key(&priv_key).unwrap();
+
+        let sig1 = algorithm.sign(&String::from(MSG1).into_bytes(), &priv_key).unwrap();
+        let sig2 = algorithm.sign(&String::from(MSG1).into_bytes(), &priv_key).unwrap();
+        assert_eq!(sig1, sig2);
+        assert_eq!(sig1.len(), 128);
+
+        let result = algorithm.verify(
+            &sig1, &String::from(MSG1).into_bytes(), &*pub_key);
+        assert_eq!(result.unwrap(), true);
+    }
+
+    #[test]
+    fn verification_error() {
+        let algorithm = create_algorithm("ed25519").unwrap();
+
+        let priv_key = Ed25519PrivateKey::from_hex(KEY1_PRIV_HEX).unwrap();
+        let pub_key = algorithm.get_public_key(&priv_key).unwrap();
+
+        let sig = algorithm.sign(&String::from(MSG1).into_bytes(), &priv_key).unwrap();
+
+        let result = algorithm.verify(
+            &sig, &String::from("wrong message").into_bytes(), &*pub_key);
+        assert_eq!(result.unwrap(), false);
+    }
+
+    #[test]
+    fn check_invalid_length() {
+        let mut too_short = KEY1_PRIV_HEX.to_string();
+        too_short.truncate(10);
+        assert!(Ed25519PrivateKey::from_hex(&too_short).is_err());
+
+        let mut pub_too_short = KEY1_PRIV_HEX.to_string();
+        pub_too_short.truncate(10);
+        assert!(Ed25519PublicKey::from_hex(&pub_too_short).is_err());
+    }
+
+    struct OtherAlgorithmKey;
+
+    impl PrivateKey for OtherAlgorithmKey {
+        fn get_algorithm_name(&self) -> &str { "other" }
+        fn as_hex(&self) -> String { String::new() }
+        fn as_slice(&self) -> &[u8] { &[] }
+    }
+
+    impl PublicKey for OtherAlgorithmKey {
+        fn get_algorithm_name(&self) -> &str { "other" }
+        fn as_hex(&self) -> String { String::new() }
+        fn as_slice(&self) -> &[u8] { &[] }
+    }
+
+    #[test]
+    fn rejects_mismatched_key_algorithm() {
+        let algorithm = create_algorithm("ed25519").unwrap();
+
+        let other_key = OtherAlgorithmKey;
+        assert!(algorithm.sign(&String::from(MSG1).into_bytes(), &other_key).is_err());
+        assert!(algorithm.get_public_key(&other_key).is_err());
+        assert!(algorithm.verify("00", &String::from(MSG1).into_bytes(), &other_key).is_err());
+    }
+}
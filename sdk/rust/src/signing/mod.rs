@@ -0,0 +1,213 @@
+mod secp256k1;
+mod ed25519;
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::ptr;
+use std::sync::atomic;
+
+#[derive(Debug)]
+pub enum Error {
+    ParseError(String),
+    SigningError(Box<StdError>),
+    Error(String)
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::ParseError(ref msg) => msg,
+            Error::SigningError(ref err) => err.description(),
+            Error::Error(ref msg) => msg
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::ParseError(ref s) => write!(f, "ParseError: {}", s),
+            Error::SigningError(ref err) => write!(f, "SigningError: {}", err.description()),
+            Error::Error(ref s) => write!(f, "Error: {}", s)
+        }
+    }
+}
+
+/// The message digest used before signing/verification. `Sha256` remains
+/// the default so existing deployments keep producing and accepting the
+/// same signatures.
+///
+/// secp256k1 is the only algorithm here that takes a `HashAlgorithm`.
+/// Its message is fixed at 32 bytes, so it rejects `Sha512`: signing,
+/// verifying and key recovery return an error instead of truncating the
+/// digest, since a truncated SHA-512 digest is not stronger than SHA-256.
+/// Ed25519 does not take a `HashAlgorithm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha512
+}
+
+impl HashAlgorithm {
+    /// The algorithms in order of preference, most broadly compatible first.
+    pub fn preference_order() -> &'static [HashAlgorithm] {
+        &[HashAlgorithm::Sha256, HashAlgorithm::Sha512]
+    }
+}
+
+impl Default for HashAlgorithm {
+    fn default() -> Self {
+        HashAlgorithm::Sha256
+    }
+}
+
+pub trait PrivateKey {
+    fn get_algorithm_name(&self) -> &str;
+    fn as_hex(&self) -> String;
+    fn as_slice(&self) -> &[u8];
+}
+
+pub trait PublicKey {
+    fn get_algorithm_name(&self) -> &str;
+    fn as_hex(&self) -> String;
+    fn as_slice(&self) -> &[u8];
+}
+
+pub trait Algorithm {
+    fn get_name(&self) -> &str;
+
+    fn sign(&self, message: &[u8], key: &PrivateKey) -> Result<String, Error>;
+
+    /// Signs `message` and returns a signature from which the signer's
+    /// public key can later be recovered via `recover_public_key`.
+    ///
+    /// Algorithms that do not support key recovery may leave this at its
+    /// default, which simply reports the algorithm as unsupported.
+    fn sign_recoverable(&self, _message: &[u8], _key: &PrivateKey) -> Result<String, Error> {
+        Err(Error::Error(
+            format!("{} does not support recoverable signatures", self.get_name())))
+    }
+
+    fn verify(&self, signature: &str, message: &[u8], key: &PublicKey) -> Result<bool, Error>;
+
+    /// Recovers the public key of the signer of a recoverable signature
+    /// produced by `sign_recoverable`.
+    fn recover_public_key(&self, _signature: &str, _message: &[u8]) -> Result<Box<PublicKey>, Error> {
+        Err(Error::Error(
+            format!("{} does not support public key recovery", self.get_name())))
+    }
+
+    fn get_public_key(&self, private_key: &PrivateKey) -> Result<Box<PublicKey>, Error>;
+}
+
+pub fn create_algorithm(algorithm_name: &str) -> Result<Box<Algorithm>, Error> {
+    match algorithm_name {
+        "secp256k1" => Ok(Box::new(secp256k1::Secp256k1Algorithm::new())),
+        "ed25519" => Ok(Box::new(ed25519::Ed25519Algorithm::new())),
+        _ => Err(Error::Error(format!("no such algorithm: {}", algorithm_name)))
+    }
+}
+
+fn hex_str_to_bytes(s: &str) -> Result<Vec<u8>, Error> {
+    for (i, ch) in s.chars().enumerate() {
+        if !ch.is_digit(16) {
+            return Err(Error::ParseError(format!("invalid character position {}", i)));
+        }
+    }
+
+    let input: Vec<_> = s.chars().collect();
+
+    let decoded: Vec<u8> = input.chunks(2).map(|chunk| {
+        ((chunk[0].to_digit(16).unwrap() << 4) |
+        (chunk[1].to_digit(16).unwrap())) as u8
+    }).collect();
+
+    return Ok(decoded);
+}
+
+/// Checks that a key was produced by the same algorithm that is about to
+/// use it. Private and public keys are both just raw bytes, so nothing
+/// else stops a secp256k1 key from being handed to the ed25519 algorithm
+/// (or vice versa) and silently producing a meaningless signature.
+fn check_key_algorithm(expected: &str, key_algorithm: &str) -> Result<(), Error> {
+    if key_algorithm != expected {
+        return Err(Error::Error(
+            format!("key algorithm '{}' does not match '{}'", key_algorithm, expected)));
+    }
+    Ok(())
+}
+
+fn bytes_to_hex_str(b: &[u8]) -> String {
+    b.iter()
+     .map(|b| format!("{:02x}", b))
+     .collect::<Vec<_>>()
+     .join("")
+}
+
+/// A byte buffer that is guaranteed to be overwritten with zeros when
+/// dropped, for holding private key material that should not linger in
+/// heap memory after it goes out of scope.
+pub struct Zeroizing {
+    bytes: Vec<u8>
+}
+
+impl Zeroizing {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Zeroizing { bytes: bytes }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn as_hex(&self) -> String {
+        bytes_to_hex_str(&self.bytes)
+    }
+}
+
+impl Drop for Zeroizing {
+    fn drop(&mut self) {
+        for byte in self.bytes.iter_mut() {
+            unsafe { ptr::write_volatile(byte, 0); }
+        }
+        atomic::fence(atomic::Ordering::SeqCst);
+    }
+}
+
+pub struct Signer<'a> {
+    factory: &'a CryptoFactory<'a>,
+    private_key: &'a PrivateKey
+}
+
+impl<'a> Signer<'a> {
+    pub fn new(factory: &'a CryptoFactory, private_key: &'a PrivateKey) -> Self {
+        Signer {
+            factory: factory,
+            private_key: private_key
+        }
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Result<String, Error> {
+        self.factory.algorithm.sign(message, self.private_key)
+    }
+}
+
+pub struct CryptoFactory<'a> {
+    algorithm: &'a Algorithm
+}
+
+impl<'a> CryptoFactory<'a> {
+    pub fn new(algorithm: &'a Algorithm) -> Self {
+        CryptoFactory {
+            algorithm: algorithm
+        }
+    }
+
+    pub fn get_algorithm(&self) -> &Algorithm {
+        self.algorithm
+    }
+
+    pub fn new_signer(&'a self, private_key: &'a PrivateKey) -> Signer {
+        Signer::new(self, private_key)
+    }
+}
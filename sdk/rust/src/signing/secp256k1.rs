@@ -8,10 +8,17 @@ use self::crypto::sha2::Sha256;
 use self::rust_base58::FromBase58;
 use self::rust_base58::base58::FromBase58Error;
 
+use std::sync::Arc;
+
 use super::PrivateKey;
 use super::PublicKey;
 use super::Algorithm;
 use super::Error;
+use super::hex_str_to_bytes;
+use super::bytes_to_hex_str;
+use super::check_key_algorithm;
+use super::Zeroizing;
+use super::HashAlgorithm;
 
 impl From<secp256k1::Error> for Error {
     fn from(e: secp256k1::Error) -> Self {
@@ -26,14 +33,14 @@ impl From<FromBase58Error> for Error {
 }
 
 pub struct Secp256k1PrivateKey {
-    private: Vec<u8>
+    private: Zeroizing
 }
 
 impl Secp256k1PrivateKey {
     pub fn from_hex(s: &str) -> Result<Self, Error> {
         match hex_str_to_bytes(s) {
             Ok(key_bytes) => Ok(Secp256k1PrivateKey{
-                private: key_bytes
+                private: Zeroizing::new(key_bytes)
             }),
             Err(err) => return Err(err)
         }
@@ -48,7 +55,7 @@ impl Secp256k1PrivateKey {
         b.remove(len - 4);
         b.remove(0);
         Ok(Secp256k1PrivateKey{
-            private: b
+            private: Zeroizing::new(b)
         })
     }
 }
@@ -59,11 +66,11 @@ impl PrivateKey for Secp256k1PrivateKey {
     }
 
     fn as_hex(&self) -> String {
-        bytes_to_hex_str(&self.private)
+        self.private.as_hex()
     }
 
     fn as_slice(&self) -> &[u8] {
-        return &self.private;
+        self.private.as_slice()
     }
 }
 
@@ -96,16 +103,120 @@ impl PublicKey for Secp256k1PublicKey {
     }
 }
 
+thread_local! {
+    /// A capability-free context, good only for parsing/serializing keys
+    /// and signatures. Building a context is the dominant cost of the
+    /// operations below, so this is built once per thread and reused
+    /// rather than paying for (unused) precomputation tables on every call.
+    static PARSE_CONTEXT: secp256k1::Secp256k1 =
+        secp256k1::Secp256k1::with_caps(secp256k1::ContextFlag::None);
+}
+
+fn with_parse_context<T, F: FnOnce(&secp256k1::Secp256k1) -> T>(f: F) -> T {
+    PARSE_CONTEXT.with(|context| f(context))
+}
+
+#[derive(Clone)]
 pub struct Secp256k1Algorithm {
-    context: secp256k1::Secp256k1
+    /// The signing/verification context. It carries precomputed tables
+    /// that are expensive to build but immutable once built, so it's
+    /// wrapped in an `Arc` to make cloning the algorithm cheap: signing
+    /// or verifying many transactions amortizes the table-building cost
+    /// once instead of per `Secp256k1Algorithm` instance.
+    context: Arc<secp256k1::Secp256k1>,
+    hash_algorithm: HashAlgorithm
 }
 
 impl Secp256k1Algorithm {
+    /// A context capable of both signing and verifying.
     pub fn new() -> Self {
+        Self::with_caps(secp256k1::ContextFlag::Full)
+    }
+
+    /// A context that can only sign, skipping the verification
+    /// precomputation table. Use this when a process (e.g. a transaction
+    /// submitter) only ever signs.
+    pub fn signing_only() -> Self {
+        Self::with_caps(secp256k1::ContextFlag::SignOnly)
+    }
+
+    /// A context that can only verify (and recover public keys from
+    /// recoverable signatures), skipping the signing precomputation
+    /// table. Use this when a process (e.g. a validator) only ever
+    /// verifies.
+    pub fn verification_only() -> Self {
+        Self::with_caps(secp256k1::ContextFlag::VerifyOnly)
+    }
+
+    fn with_caps(caps: secp256k1::ContextFlag) -> Self {
         Secp256k1Algorithm{
-            context: secp256k1::Secp256k1::new()
+            context: Arc::new(secp256k1::Secp256k1::with_caps(caps)),
+            hash_algorithm: HashAlgorithm::default()
+        }
+    }
+
+    /// Builds a `Secp256k1Algorithm` that hashes messages with
+    /// `hash_algorithm` instead of the default SHA-256. Signers and
+    /// verifiers that need to interoperate must agree on the same
+    /// `HashAlgorithm`. Note that `HashAlgorithm::Sha512` is rejected at
+    /// sign/verify time — see `message_hash`.
+    pub fn with_hash_algorithm(hash_algorithm: HashAlgorithm) -> Self {
+        let mut algorithm = Self::new();
+        algorithm.hash_algorithm = hash_algorithm;
+        algorithm
+    }
+
+    /// Hashes `message` with this algorithm's configured `HashAlgorithm`,
+    /// as required by secp256k1's fixed 32-byte message format.
+    ///
+    /// `HashAlgorithm::Sha512` is rejected rather than silently truncated
+    /// to 32 bytes: a truncated SHA-512 digest changes the compression
+    /// function used but does not raise the effective collision/preimage
+    /// resistance over `Sha256`, so accepting it here would let callers
+    /// believe they had opted into a stronger digest when they had not.
+    /// `Sha512` remains available to algorithms whose message can hold
+    /// its full 64-byte output.
+    fn message_hash(&self, message: &[u8]) -> Result<[u8; 32], Error> {
+        match self.hash_algorithm {
+            HashAlgorithm::Sha256 => {
+                let mut sha = Sha256::new();
+                sha.input(message);
+                let mut hash = [0u8; 32];
+                sha.result(&mut hash);
+                Ok(hash)
+            },
+            HashAlgorithm::Sha512 => Err(Error::Error(String::from(
+                "secp256k1 messages are fixed at 32 bytes, so Sha512 cannot be used at its full \
+                 digest width and is rejected rather than truncated; use Sha256 for secp256k1, or \
+                 an algorithm whose message can hold a full 64-byte digest"))),
         }
     }
+
+    /// Computes an ECDH shared secret from a local private key and a
+    /// peer's public key: the peer's point is multiplied by the local
+    /// secret scalar and the X coordinate of the resulting point is
+    /// hashed with SHA-256 to produce a 32-byte secret.
+    pub fn diffie_hellman(&self, private_key: &PrivateKey, peer_public_key: &PublicKey)
+        -> Result<Vec<u8>, Error>
+    {
+        // Parsing the keys only needs `PARSE_CONTEXT`, but the point
+        // multiplication itself needs `self.context`'s precomputed ecmult
+        // tables, which `PARSE_CONTEXT` (built with `ContextFlag::None`)
+        // does not have.
+        let sk = with_parse_context(|context|
+            secp256k1::key::SecretKey::from_slice(context, private_key.as_slice()))?;
+        let mut point = with_parse_context(|context|
+            secp256k1::key::PublicKey::from_slice(context, peer_public_key.as_slice()))?;
+        point.mul_assign(&self.context, &sk)?;
+        let uncompressed = with_parse_context(|context| point.serialize_vec(context, false));
+        let x_coordinate = &uncompressed[1..33];
+
+        let mut sha = Sha256::new();
+        sha.input(x_coordinate);
+        let mut shared_secret: &mut [u8] = & mut [0; 32];
+        sha.result(shared_secret);
+        Ok(shared_secret.to_vec())
+    }
 }
 
 impl Algorithm for Secp256k1Algorithm {
@@ -114,13 +225,12 @@ impl Algorithm for Secp256k1Algorithm {
     }
 
     fn sign(&self, message: &[u8], key: &PrivateKey) -> Result<String, Error> {
-        let mut sha = Sha256::new();
-        sha.input(message);
-        let mut hash: &mut [u8] = & mut [0; 32];
-        sha.result(hash);
+        check_key_algorithm(self.get_name(), key.get_algorithm_name())?;
+        let hash = self.message_hash(message)?;
 
-        let sk = secp256k1::key::SecretKey::from_slice(&self.context, key.as_slice())?;
-        let sig = self.context.sign(&secp256k1::Message::from_slice(hash)?, &sk)?;
+        let sk = with_parse_context(|context|
+            secp256k1::key::SecretKey::from_slice(context, key.as_slice()))?;
+        let sig = self.context.sign(&secp256k1::Message::from_slice(&hash)?, &sk)?;
         let compact = sig.serialize_compact(&self.context);
         Ok(compact.iter()
                   .map(|b| format!("{:02x}", b))
@@ -128,16 +238,32 @@ impl Algorithm for Secp256k1Algorithm {
                   .join(""))
     }
 
+    fn sign_recoverable(&self, message: &[u8], key: &PrivateKey) -> Result<String, Error> {
+        check_key_algorithm(self.get_name(), key.get_algorithm_name())?;
+        let hash = self.message_hash(message)?;
+
+        let sk = with_parse_context(|context|
+            secp256k1::key::SecretKey::from_slice(context, key.as_slice()))?;
+        let sig = self.context.sign_recoverable(&secp256k1::Message::from_slice(&hash)?, &sk)?;
+        let (recovery_id, compact) = sig.serialize_compact(&self.context);
+
+        let mut bytes = Vec::with_capacity(65);
+        bytes.push(recovery_id.to_i32() as u8);
+        bytes.extend_from_slice(&compact);
+        Ok(bytes_to_hex_str(&bytes))
+    }
+
     fn verify(&self, signature: &str, message: &[u8], key: &PublicKey) -> Result<bool, Error> {
-        let mut sha = Sha256::new();
-        sha.input(message);
-        let mut hash: &mut [u8] = & mut [0; 32];
-        sha.result(hash);
-
-        let result = self.context.verify(
-            &secp256k1::Message::from_slice(hash)?,
-            &secp256k1::Signature::from_compact(&self.context, &hex_str_to_bytes(&signature)?)?,
-            &secp256k1::key::PublicKey::from_slice(&self.context, key.as_slice())?);
+        check_key_algorithm(self.get_name(), key.get_algorithm_name())?;
+        let hash = self.message_hash(message)?;
+
+        let (sig, public_key) = with_parse_context(|context| -> Result<_, Error> {
+            let sig = secp256k1::Signature::from_compact(context, &hex_str_to_bytes(&signature)?)?;
+            let public_key = secp256k1::key::PublicKey::from_slice(context, key.as_slice())?;
+            Ok((sig, public_key))
+        })?;
+
+        let result = self.context.verify(&secp256k1::Message::from_slice(&hash)?, &sig, &public_key);
         match result {
             Ok(()) => Ok(true),
             Err(secp256k1::Error::IncorrectSignature) => Ok(false),
@@ -145,47 +271,48 @@ impl Algorithm for Secp256k1Algorithm {
         }
     }
 
-    fn get_public_key(&self, private_key: &PrivateKey) -> Result<Box<PublicKey>, Error> {
-        let sk = secp256k1::key::SecretKey::from_slice(&self.context, private_key.as_slice())?;
+    fn recover_public_key(&self, signature: &str, message: &[u8]) -> Result<Box<PublicKey>, Error> {
+        let hash = self.message_hash(message)?;
+
+        let bytes = hex_str_to_bytes(signature)?;
+        if bytes.len() != 65 {
+            return Err(Error::ParseError(
+                format!("invalid recoverable signature length: {}", bytes.len())));
+        }
+
+        let recovery_id = secp256k1::RecoveryId::from_i32(bytes[0] as i32)?;
+        let recoverable_sig = with_parse_context(|context|
+            secp256k1::RecoverableSignature::from_compact(context, &bytes[1..], recovery_id))?;
+        let pk = self.context.recover(
+            &secp256k1::Message::from_slice(&hash)?, &recoverable_sig)?;
+
         let result = Secp256k1PublicKey::from_hex(
-            bytes_to_hex_str(
-                &secp256k1::key::PublicKey::from_secret_key(
-                    &self.context, &sk)?.serialize_vec(&self.context, true)).as_str());
+            with_parse_context(|context| bytes_to_hex_str(&pk.serialize_vec(context, true))).as_str());
         match result {
             Err(err) => Err(err),
             Ok(pk) => Ok(Box::new(pk))
         }
     }
-}
 
-fn hex_str_to_bytes(s: &str) -> Result<Vec<u8>, Error> {
-    for (i, ch) in s.chars().enumerate() {
-        if !ch.is_digit(16) {
-            return Err(Error::ParseError(format!("invalid character position {}", i)));
+    fn get_public_key(&self, private_key: &PrivateKey) -> Result<Box<PublicKey>, Error> {
+        check_key_algorithm(self.get_name(), private_key.get_algorithm_name())?;
+        let sk = with_parse_context(|context|
+            secp256k1::key::SecretKey::from_slice(context, private_key.as_slice()))?;
+        let public_key = secp256k1::key::PublicKey::from_secret_key(&self.context, &sk)?;
+        let result = Secp256k1PublicKey::from_hex(
+            with_parse_context(|context| bytes_to_hex_str(&public_key.serialize_vec(context, true))).as_str());
+        match result {
+            Err(err) => Err(err),
+            Ok(pk) => Ok(Box::new(pk))
         }
     }
-
-    let input: Vec<_> = s.chars().collect();
-
-    let decoded: Vec<u8> = input.chunks(2).map(|chunk| {
-        ((chunk[0].to_digit(16).unwrap() << 4) |
-        (chunk[1].to_digit(16).unwrap())) as u8
-    }).collect();
-
-    return Ok(decoded);
-}
-
-fn bytes_to_hex_str(b: &[u8]) -> String {
-    b.iter()
-     .map(|b| format!("{:02x}", b))
-     .collect::<Vec<_>>()
-     .join("")
 }
 
 #[cfg(test)]
 mod secp256k1_test {
     use super::Secp256k1PrivateKey;
     use super::Secp256k1PublicKey;
+    use super::super::Algorithm;
     use super::super::CryptoFactory;
     use super::super::PrivateKey;
     use super::super::PublicKey;
@@ -319,6 +446,89 @@ mod secp256k1_test {
         assert_eq!(result.unwrap(), true);
     }
 
+    #[test]
+    fn sha512_is_rejected_for_fixed_width_messages() {
+        use super::Secp256k1Algorithm;
+        use super::super::HashAlgorithm;
+
+        let algorithm = Secp256k1Algorithm::with_hash_algorithm(HashAlgorithm::Sha512);
+        assert_eq!(algorithm.get_name(), "secp256k1");
+
+        let priv_key = Secp256k1PrivateKey::from_hex(KEY1_PRIV_HEX).unwrap();
+        let pub_key = Secp256k1PublicKey::from_hex(KEY1_PUB_HEX).unwrap();
+
+        // secp256k1's message is fixed at 32 bytes, so a truncated SHA-512
+        // digest would not provide a stronger margin than SHA-256 — sign
+        // and verify must refuse rather than silently truncate.
+        assert!(algorithm.sign(&String::from(MSG1).into_bytes(), &priv_key).is_err());
+        assert!(
+            algorithm.verify(MSG1_KEY1_SIG, &String::from(MSG1).into_bytes(), &pub_key).is_err());
+    }
+
+    #[test]
+    fn capability_scoped_contexts() {
+        use super::Secp256k1Algorithm;
+
+        let priv_key = Secp256k1PrivateKey::from_hex(KEY1_PRIV_HEX).unwrap();
+        let pub_key = Secp256k1PublicKey::from_hex(KEY1_PUB_HEX).unwrap();
+
+        let signer = Secp256k1Algorithm::signing_only();
+        let signature = signer.sign(&String::from(MSG1).into_bytes(), &priv_key).unwrap();
+        assert_eq!(signature, MSG1_KEY1_SIG);
+
+        let verifier = Secp256k1Algorithm::verification_only();
+        let result = verifier.verify(&signature, &String::from(MSG1).into_bytes(), &pub_key);
+        assert_eq!(result.unwrap(), true);
+    }
+
+    #[test]
+    fn algorithm_is_cheaply_cloneable() {
+        use super::Secp256k1Algorithm;
+
+        let algorithm = Secp256k1Algorithm::new();
+        let cloned = algorithm.clone();
+        assert!(::std::sync::Arc::ptr_eq(&algorithm.context, &cloned.context));
+
+        let priv_key = Secp256k1PrivateKey::from_hex(KEY1_PRIV_HEX).unwrap();
+        let signature = cloned.sign(&String::from(MSG1).into_bytes(), &priv_key).unwrap();
+        assert_eq!(signature, MSG1_KEY1_SIG);
+    }
+
+    #[test]
+    fn diffie_hellman_shared_secret_matches() {
+        use super::Secp256k1Algorithm;
+
+        let algorithm = Secp256k1Algorithm::new();
+
+        let priv_key1 = Secp256k1PrivateKey::from_hex(KEY1_PRIV_HEX).unwrap();
+        let pub_key1 = Secp256k1PublicKey::from_hex(KEY1_PUB_HEX).unwrap();
+
+        let priv_key2 = Secp256k1PrivateKey::from_hex(KEY2_PRIV_HEX).unwrap();
+        let pub_key2 = Secp256k1PublicKey::from_hex(KEY2_PUB_HEX).unwrap();
+
+        let secret1 = algorithm.diffie_hellman(&priv_key1, &pub_key2).unwrap();
+        let secret2 = algorithm.diffie_hellman(&priv_key2, &pub_key1).unwrap();
+
+        assert_eq!(secret1, secret2);
+        assert_eq!(secret1.len(), 32);
+    }
+
+    #[test]
+    fn recoverable_signing_and_recovery() {
+        let algorithm = create_algorithm("secp256k1").unwrap();
+        assert_eq!(algorithm.get_name(), "secp256k1");
+
+        let priv_key1 = Secp256k1PrivateKey::from_hex(KEY1_PRIV_HEX).unwrap();
+        let pub_key1 = algorithm.get_public_key(&priv_key1).unwrap();
+
+        let signature = algorithm.sign_recoverable(
+            &String::from(MSG1).into_bytes(), &priv_key1).unwrap();
+        let recovered = algorithm.recover_public_key(
+            &signature, &String::from(MSG1).into_bytes()).unwrap();
+
+        assert_eq!(recovered.as_hex(), pub_key1.as_hex());
+    }
+
     #[test]
     fn verification_error() {
         let algorithm = create_algorithm("secp256k1").unwrap();
@@ -334,4 +544,29 @@ mod secp256k1_test {
                                       &pub_key1);
         assert_eq!(result.unwrap(), false);
     }
+
+    struct OtherAlgorithmKey;
+
+    impl PrivateKey for OtherAlgorithmKey {
+        fn get_algorithm_name(&self) -> &str { "other" }
+        fn as_hex(&self) -> String { String::new() }
+        fn as_slice(&self) -> &[u8] { &[] }
+    }
+
+    impl PublicKey for OtherAlgorithmKey {
+        fn get_algorithm_name(&self) -> &str { "other" }
+        fn as_hex(&self) -> String { String::new() }
+        fn as_slice(&self) -> &[u8] { &[] }
+    }
+
+    #[test]
+    fn rejects_mismatched_key_algorithm() {
+        let algorithm = create_algorithm("secp256k1").unwrap();
+
+        let other_key = OtherAlgorithmKey;
+        assert!(algorithm.sign(&String::from(MSG1).into_bytes(), &other_key).is_err());
+        assert!(algorithm.get_public_key(&other_key).is_err());
+        assert!(
+            algorithm.verify(MSG1_KEY1_SIG, &String::from(MSG1).into_bytes(), &other_key).is_err());
+    }
 }